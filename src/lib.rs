@@ -1,6 +1,8 @@
 extern crate agb_core;
 extern crate rand;
 
+pub mod libretro;
+
 use std::os::raw::c_char;
 use std::sync::atomic::AtomicBool;
 use std::collections::HashMap;
@@ -17,10 +19,50 @@ pub use agb_core::{Key, WIDTH, HEIGHT};
 
 const GAME_START_STATE: &'static [u8] = include_bytes!("game_start.state");
 
+/// The address execution jumps to once a game of Tetris is over; also used as the end-of-game
+/// breakpoint address.
+const END_OF_GAME_ADDRESS: u16 = 0x6803;
+
+/// The playfield is 10 columns by 18 visible rows, laid out one byte per cell in WRAM.
+const BOARD_WIDTH: usize = 10;
+const BOARD_HEIGHT: usize = 18;
+const BOARD_SIZE: usize = BOARD_WIDTH * BOARD_HEIGHT;
+const BOARD_BASE_ADDRESS: u16 = 0xC802;
+
+/// Tile id written into the playfield's tile map for an empty cell.
+const EMPTY_BOARD_TILE: u8 = 0x2F;
+
+const CURRENT_PIECE_ID_ADDRESS: u16 = 0xC203;
+const CURRENT_PIECE_ROTATION_ADDRESS: u16 = 0xC204;
+const NEXT_PIECE_ID_ADDRESS: u16 = 0xC213;
+const LEVEL_ADDRESS: u16 = 0xFFA4;
+
+/// A falling or on-deck Tetris piece, as reported by `Environment::get_current_piece` and
+/// `Environment::get_next_piece`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Piece {
+	pub id: u8,
+	pub rotation: u8
+}
+
+/// The buttons `Environment::step` understands, in bit order least-significant-first.
+const STEP_KEYS: [Key; 8] = [Key::A, Key::B, Key::Select, Key::Start, Key::Up, Key::Down, Key::Left, Key::Right];
+
+/// The outcome of advancing the emulator by one or more frames with `Environment::step`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepResult {
+	pub score_delta: i32,
+	pub lines_delta: i32,
+	pub done: bool
+}
+
 pub struct Environment {
 	gameboy: Box<Gameboy>,
 	keys: HashMap<Key, bool>,
-	running: Arc<AtomicBool>
+	running: Arc<AtomicBool>,
+	seed: u16
 }
 
 impl Environment {
@@ -38,7 +80,7 @@ impl Environment {
 		let mut gameboy = Box::new(Gameboy::new(buf.into_boxed_slice(), None)?);
 		// set end of game breakpoint
 		gameboy.debugger.enable();
-		gameboy.add_breakpoint(Breakpoint::new(0x6803, AccessType::Execute));
+		gameboy.add_breakpoint(Breakpoint::new(END_OF_GAME_ADDRESS, AccessType::Execute));
 		{
 			let running = running.clone();
 			gameboy.register_breakpoint_callback(move |_bp| {
@@ -61,23 +103,66 @@ impl Environment {
 		Ok(Environment {
 			gameboy,
 			keys,
-			running
+			running,
+			seed: 0
 		})
 	}
 
 	pub fn start_episode(&mut self) -> Result<(), Box<Error>> {
-		use std::sync::atomic::Ordering;
 		use rand::{thread_rng, Rng};
+
+		let seed = thread_rng().gen_range(0, 0xFFFF);
+		self.start_episode_seeded(seed)
+	}
+
+	/// Start a new episode with a caller-supplied DIV seed instead of a random one.
+	///
+	/// This makes episodes reproducible: replaying the same seed against the same code
+	/// version yields byte-identical emulation, which is what diffing agent behaviour
+	/// across changes (or comparing benchmark runs) depends on.
+	pub fn start_episode_seeded(&mut self, seed: u16) -> Result<(), Box<Error>> {
+		use std::sync::atomic::Ordering;
 		use agb_core::gameboy::debugger::{DebuggerInterface};
 
 		self.gameboy.load_state(GAME_START_STATE)?;
-		let seed = thread_rng().gen_range(0, 0xFFFF);
 		self.gameboy.set_div(seed);
+		self.seed = seed;
 		self.running.store(true, Ordering::Relaxed);
 
 		Ok(())
 	}
 
+	/// The DIV seed used to start the current (or most recently started) episode.
+	pub fn get_seed(&self) -> u16 {
+		self.seed
+	}
+
+	/// Dump the current emulator state to a buffer that can later be handed to `load_state`.
+	///
+	/// This snapshots the full Gameboy state (cpu, memory, ppu, etc), not just the parts of
+	/// WRAM that `get_score`/`get_lines` inspect, so it can be used to resume emulation from
+	/// exactly where it left off.
+	pub fn save_state(&self) -> Box<[u8]> {
+		self.gameboy.dump_state()
+	}
+
+	/// Restore a previously dumped emulator state.
+	///
+	/// Since the state may have been captured at the end-of-game breakpoint (or anywhere else),
+	/// `running` is re-derived from the restored cpu state rather than left at its old value.
+	pub fn load_state(&mut self, state: &[u8]) -> Result<(), Box<Error>> {
+		use std::sync::atomic::Ordering;
+		use agb_core::gameboy::debugger::{DebuggerInterface};
+
+		self.gameboy.load_state(state)?;
+		// mirrors the breakpoint condition in Environment::new: terminal iff execution is
+		// sitting exactly at the end-of-game address, not merely past it
+		let is_terminal = self.gameboy.pc() == END_OF_GAME_ADDRESS;
+		self.running.store(!is_terminal, Ordering::Relaxed);
+
+		Ok(())
+	}
+
 	pub fn run_frame(&mut self) {
 		use std::time::Duration;
 		use agb_core::FPS;
@@ -139,6 +224,84 @@ impl Environment {
 		lines
 	}
 
+	/// Reads the playfield out of WRAM into a fixed 10x18 (row-major) tile map, normalized so
+	/// that empty cells are 0 and filled cells are 1.
+	///
+	/// This gives a symbolic observation of the board that tabular/feature-based agents can
+	/// consume directly, without having to infer cell occupancy from pixels.
+	pub fn get_board(&self) -> [u8; BOARD_SIZE] {
+		use agb_core::gameboy::debugger::DebuggerInterface;
+
+		let mut board = [0u8; BOARD_SIZE];
+		for i in 0..BOARD_SIZE {
+			let tile = self.gameboy.read_memory(BOARD_BASE_ADDRESS + i as u16);
+			board[i] = if tile == EMPTY_BOARD_TILE { 0 } else { 1 };
+		}
+
+		board
+	}
+
+	/// The piece currently falling, read directly out of WRAM.
+	pub fn get_current_piece(&self) -> Piece {
+		use agb_core::gameboy::debugger::DebuggerInterface;
+
+		Piece {
+			id: self.gameboy.read_memory(CURRENT_PIECE_ID_ADDRESS),
+			rotation: self.gameboy.read_memory(CURRENT_PIECE_ROTATION_ADDRESS)
+		}
+	}
+
+	/// The piece shown in the "next" preview.
+	///
+	/// Unlike the falling piece, the preview is always rendered in its spawn orientation — the
+	/// game does not keep rotation state for it — so `rotation` is always 0 rather than being
+	/// read out of RAM.
+	pub fn get_next_piece(&self) -> Piece {
+		use agb_core::gameboy::debugger::DebuggerInterface;
+
+		Piece {
+			id: self.gameboy.read_memory(NEXT_PIECE_ID_ADDRESS),
+			rotation: 0
+		}
+	}
+
+	/// The current level.
+	pub fn get_level(&self) -> i32 {
+		use agb_core::gameboy::debugger::DebuggerInterface;
+
+		self.gameboy.read_memory(LEVEL_ADDRESS) as i32
+	}
+
+	/// Applies a button bitmask once (bit order: `STEP_KEYS`), then emulates up to `frames`
+	/// frames, stopping early if the game ends, and reports the score/line deltas and whether
+	/// the episode is now over.
+	///
+	/// This collapses the usual `set_key_state`/`run_frame`/`is_running`/`get_score`/`get_lines`
+	/// dance into a single call, which both standardizes reward accounting and avoids paying
+	/// the FFI round-trip cost once per emulated frame.
+	pub fn step(&mut self, keys: u16, frames: u32) -> StepResult {
+		let pre_score = self.get_score();
+		let pre_lines = self.get_lines();
+
+		for (i, key) in STEP_KEYS.iter().enumerate() {
+			let pressed = (keys & (1 << i)) != 0;
+			self.set_key_state(key.clone(), pressed);
+		}
+
+		for _ in 0..frames {
+			if !self.is_running() {
+				break;
+			}
+			self.run_frame();
+		}
+
+		StepResult {
+			score_delta: self.get_score() - pre_score,
+			lines_delta: self.get_lines() - pre_lines,
+			done: !self.is_running()
+		}
+	}
+
 	pub fn set_key_state(&mut self, key: Key, pressed: bool) {
 		if let Some(state) = self.keys.get_mut(&key) {
 			if *state != pressed {
@@ -153,6 +316,29 @@ impl Environment {
 		}
 	}
 
+	/// Returns an array of single-byte grayscale pixels (one byte per pixel, 160*144 bytes total).
+	///
+	/// The DMG only ever displays 4 shades, so this is 4x more compact than the RGBA framebuffer
+	/// from `get_pixels` (3x more compact than `rgb_pixels`) and
+	/// skips a conversion step that convolutional front-ends would otherwise have to do
+	/// themselves. Each shade is picked out of the (achromatic) framebuffer by its red
+	/// component's high nibble, which is one of 0x0, 0x5, 0xA, 0xF for the four DMG shades.
+	/// When `scaled` is true the output spans the full 0-255 range ({0, 85, 170, 255}),
+	/// otherwise it is the raw 2-bit shade index ({0, 1, 2, 3}).
+	pub fn gray_pixels(&self, scaled: bool) -> Box<[u8]> {
+		let rgba = self.gameboy.get_framebuffer();
+
+		let mut gray = Vec::with_capacity(rgba.len());
+		for pixel in rgba {
+			let red = (*pixel >> 24) as u8;
+			let shade = (red >> 4) / 5; // 0-3
+
+			gray.push(if scaled { shade * 85 } else { shade });
+		}
+
+		gray.into_boxed_slice()
+	}
+
 	/// Returns an array of RGB bytes (each component is 8-bits)
 	pub fn rgb_pixels(&self) -> Box<[u8]> {
 		let rgba = self.gameboy.get_framebuffer();
@@ -232,6 +418,25 @@ pub unsafe extern "C" fn start_episode(env_ptr: *mut Environment) -> i32 {
 	}
 }
 
+/// Start a new game of Tetris using a caller-supplied DIV seed instead of a random one.
+///
+/// This makes the episode reproducible: starting an episode with the same seed always
+/// produces the same sequence of frames given the same inputs.
+#[no_mangle]
+pub unsafe extern "C" fn start_episode_seeded(env_ptr: *mut Environment, seed: u16) -> i32 {
+	if env_ptr == ptr::null_mut() {
+		abort();
+	}
+
+	let environment = &mut *env_ptr;
+	if environment.start_episode_seeded(seed).is_err() {
+		-1
+	}
+	else {
+		0
+	}
+}
+
 /// Run a single frame of the game
 #[no_mangle]
 pub unsafe extern "C" fn run_frame(env_ptr: *mut Environment) {
@@ -295,6 +500,41 @@ pub unsafe extern "C" fn free_rgb_pixel_array(buffer: *mut u8) {
 	Box::from_raw(s);
 }
 
+/// Returns a pointer to an array holding WIDTH * HEIGHT single-byte grayscale pixels.
+/// the length of the array is WIDTH * HEIGHT bytes
+/// the array returned by this function must be freed by the free_gray_pixel_array function
+#[no_mangle]
+pub unsafe extern "C" fn get_gray_pixels(env_ptr: *mut Environment, scaled: bool) -> *mut u8 {
+	if env_ptr == ptr::null_mut() {
+		abort();
+	}
+	else {
+		let environment = & *env_ptr;
+		let slice = Box::into_raw(environment.gray_pixels(scaled));
+		let s: &mut[u8] = &mut*slice;
+		s.as_mut_ptr()
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn free_gray_pixel_array(buffer: *mut u8) {
+	use std::slice;
+	let s = slice::from_raw_parts_mut(buffer, WIDTH * HEIGHT);
+	Box::from_raw(s);
+}
+
+/// Applies `key_mask` (bit order: A, B, Select, Start, Up, Down, Left, Right) and emulates up
+/// to `frames` frames, short-circuiting if the game ends, writing the outcome into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn step(env_ptr: *mut Environment, key_mask: u16, frames: u32, out: *mut StepResult) {
+	if env_ptr == ptr::null_mut() || out == ptr::null_mut() {
+		abort();
+	}
+
+	let environment = &mut *env_ptr;
+	*out = environment.step(key_mask, frames);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn set_key_state(env_ptr: *mut Environment, key: Key, pressed: bool) {
 	if env_ptr == ptr::null_mut() {
@@ -305,6 +545,54 @@ pub unsafe extern "C" fn set_key_state(env_ptr: *mut Environment, key: Key, pres
 	environment.set_key_state(key, pressed);
 }
 
+/// Dump the current emulator state into a freshly allocated buffer.
+///
+/// ARGS:
+///     out_len: out-parameter, set to the length in bytes of the returned buffer.
+///
+/// Returns:
+///     A pointer to the state buffer, which must later be released with `free_state_buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn save_state(env_ptr: *mut Environment, out_len: *mut usize) -> *mut u8 {
+	if env_ptr == ptr::null_mut() || out_len == ptr::null_mut() {
+		abort();
+	}
+
+	let environment = & *env_ptr;
+	let state = environment.save_state();
+	*out_len = state.len();
+	Box::into_raw(state) as *mut u8
+}
+
+/// Free a state buffer previously returned by `save_state`.
+#[no_mangle]
+pub unsafe extern "C" fn free_state_buffer(buffer: *mut u8, len: usize) {
+	use std::slice;
+	let s = slice::from_raw_parts_mut(buffer, len);
+	Box::from_raw(s);
+}
+
+/// Restore an emulator state previously obtained from `save_state`.
+///
+/// Returns 0 on success, or -1 if the buffer was not a valid state.
+#[no_mangle]
+pub unsafe extern "C" fn load_state(env_ptr: *mut Environment, buf: *const u8, len: usize) -> i32 {
+	use std::slice;
+
+	if env_ptr == ptr::null_mut() || buf == ptr::null() {
+		abort();
+	}
+
+	let environment = &mut *env_ptr;
+	let state = slice::from_raw_parts(buf, len);
+	if environment.load_state(state).is_err() {
+		-1
+	}
+	else {
+		0
+	}
+}
+
 /// Get the score from a game of tetris that just ended.
 /// The score is stored as a 3-byte little endian bcd at address 0xC0A0
 #[no_mangle]
@@ -327,3 +615,53 @@ pub unsafe extern "C" fn get_lines(env_ptr: *const Environment) -> i32 {
 	let environment = & *env_ptr;
 	environment.get_lines()
 }
+
+/// Reads the playfield into `out`, which must point to a buffer of at least
+/// BOARD_WIDTH * BOARD_HEIGHT (10 * 18 = 180) bytes, row-major, 0 for empty and 1 for
+/// filled cells.
+#[no_mangle]
+pub unsafe extern "C" fn get_board(env_ptr: *const Environment, out: *mut u8) {
+	use std::slice;
+
+	if env_ptr == ptr::null() || out == ptr::null_mut() {
+		abort();
+	}
+
+	let environment = & *env_ptr;
+	let board = environment.get_board();
+	let out = slice::from_raw_parts_mut(out, BOARD_SIZE);
+	out.copy_from_slice(&board);
+}
+
+/// Writes the currently falling piece's id and rotation into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn get_current_piece(env_ptr: *const Environment, out: *mut Piece) {
+	if env_ptr == ptr::null() || out == ptr::null_mut() {
+		abort();
+	}
+
+	let environment = & *env_ptr;
+	*out = environment.get_current_piece();
+}
+
+/// Writes the "next" preview piece's id and rotation into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn get_next_piece(env_ptr: *const Environment, out: *mut Piece) {
+	if env_ptr == ptr::null() || out == ptr::null_mut() {
+		abort();
+	}
+
+	let environment = & *env_ptr;
+	*out = environment.get_next_piece();
+}
+
+/// Get the current level.
+#[no_mangle]
+pub unsafe extern "C" fn get_level(env_ptr: *const Environment) -> i32 {
+	if env_ptr == ptr::null() {
+		abort();
+	}
+
+	let environment = & *env_ptr;
+	environment.get_level()
+}